@@ -0,0 +1,132 @@
+//! Bridge from this crate's [`ByteAllocator`]/[`PageAllocator`] traits to
+//! the `allocator-api2` [`Allocator`] trait, so an `EarlyAllocator`-backed
+//! region can be used as the storage for any `allocator-api2`-aware
+//! container (e.g. `hashbrown::HashMap::with_hasher_in`).
+
+use core::alloc::Layout;
+use core::cell::RefCell;
+use core::ptr::NonNull;
+
+use allocator::{ByteAllocator, PageAllocator};
+use allocator_api2::alloc::{AllocError, Allocator};
+
+/// Wraps a `&RefCell<A>` so its `ByteAllocator`/`PageAllocator` impl can be
+/// reached through `allocator-api2`'s shared-reference `Allocator` trait.
+///
+/// Requests of at least `A::PAGE_SIZE` bytes are routed to the page path;
+/// smaller ones go through the byte path. This is single-threaded, matching
+/// how `EarlyAllocator` itself is used during early boot.
+pub struct AllocApi2Adapter<'a, A> {
+    inner: &'a RefCell<A>,
+}
+
+impl<'a, A> AllocApi2Adapter<'a, A> {
+    pub fn new(inner: &'a RefCell<A>) -> Self {
+        Self { inner }
+    }
+}
+
+unsafe impl<'a, A> Allocator for AllocApi2Adapter<'a, A>
+where
+    A: ByteAllocator + PageAllocator,
+{
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let mut alloc = self.inner.borrow_mut();
+        let ptr = if layout.size() >= A::PAGE_SIZE {
+            let num_pages = layout.size().div_ceil(A::PAGE_SIZE);
+            let align = layout.align().max(A::PAGE_SIZE);
+            let addr = alloc
+                .alloc_pages(num_pages, align)
+                .map_err(|_| AllocError)?;
+            NonNull::new(addr as *mut u8).ok_or(AllocError)?
+        } else {
+            alloc.alloc(layout).map_err(|_| AllocError)?
+        };
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let mut alloc = self.inner.borrow_mut();
+        if layout.size() >= A::PAGE_SIZE {
+            let num_pages = layout.size().div_ceil(A::PAGE_SIZE);
+            alloc.dealloc_pages(ptr.as_ptr() as usize, num_pages);
+        } else {
+            alloc.dealloc(ptr, layout);
+        }
+    }
+}
+
+impl<'a, A> Clone for AllocApi2Adapter<'a, A> {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use allocator::BaseAllocator;
+    use crate::EarlyAllocator;
+
+    // `axstd::MyHashMap` is this adapter's real-world caller, but axstd
+    // depends on this crate rather than the other way around, so it can't
+    // be used from here. `hashbrown::HashMap` is exactly what `MyHashMap`
+    // wraps and shares the same `allocator_api2::alloc::Allocator` bound,
+    // so driving one directly over `AllocApi2Adapter` exercises the same
+    // `allocate`/`deallocate` calls a real `MyHashMap::new_in` would.
+    // (Requires `hashbrown` as a dev-dependency.)
+
+    #[derive(Default)]
+    struct FnvHasher(u64);
+
+    impl core::hash::Hasher for FnvHasher {
+        fn write(&mut self, bytes: &[u8]) {
+            let mut hash = if self.0 == 0 { 0xcbf2_9ce4_8422_2325 } else { self.0 };
+            for &byte in bytes {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+            self.0 = hash;
+        }
+
+        fn finish(&self) -> u64 {
+            self.0
+        }
+    }
+
+    type Hasher = core::hash::BuildHasherDefault<FnvHasher>;
+
+    #[repr(align(4096))]
+    struct AlignedBuf([u8; 8192]);
+
+    #[test]
+    fn hashmap_over_the_adapter_survives_growth_and_crosses_the_page_threshold() {
+        let mut buf = AlignedBuf([0u8; 8192]);
+        let mut early = EarlyAllocator::<16>::new();
+        early.init(buf.0.as_mut_ptr() as usize, buf.0.len());
+        let cell = RefCell::new(early);
+        let adapter = AllocApi2Adapter::new(&cell);
+
+        let mut map: hashbrown::HashMap<i32, i32, Hasher, AllocApi2Adapter<EarlyAllocator<16>>> =
+            hashbrown::HashMap::with_hasher_in(Hasher::default(), adapter);
+
+        // PAGE_SIZE is 16 bytes here, so the table's backing allocation
+        // crosses from the byte path to the page path (and back again via
+        // `deallocate` on every regrow) well before this loop finishes.
+        for i in 0..200 {
+            map.insert(i, i * 2);
+        }
+        assert!(cell.borrow().used_pages() > 0);
+
+        for i in 0..200 {
+            assert_eq!(map.get(&i), Some(&(i * 2)));
+        }
+
+        for i in 0..100 {
+            assert_eq!(map.remove(&i), Some(i * 2));
+        }
+        assert_eq!(map.len(), 100);
+        assert!(!map.contains_key(&0));
+        assert_eq!(map.get(&150), Some(&300));
+    }
+}
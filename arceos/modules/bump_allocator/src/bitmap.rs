@@ -0,0 +1,360 @@
+//! Hierarchical bitmaps used to track individually reclaimable pages.
+//!
+//! This mirrors the classic tiny-OS "cascade" bitmap allocator: a leaf
+//! `Bitmap32` tracks 32 bits directly, and a generic `Bitmap<B>` adds one
+//! more level of 32-way fan-out on top of any child that implements
+//! [`BitAlloc`]. A set summary bit means "this whole child is full", so a
+//! search for a free slot never has to look past the first clear summary
+//! bit.
+
+/// A fixed-capacity allocator over a contiguous range of bit indices.
+///
+/// Indices are counted from the most-significant bit, i.e. index `0` is
+/// `1 << (CAP - 1)`. This makes "find the first free index" the same
+/// operation as "find the first clear bit from the top", which is exactly
+/// what `leading_zeros()` computes.
+pub trait BitAlloc: Default + Copy {
+    /// Total number of bits this allocator can track.
+    const CAP: usize;
+
+    /// Allocate the first free bit, returning its index.
+    fn alloc(&mut self) -> Option<usize>;
+
+    /// Allocate `size` contiguous free bits aligned to `1 << align_log2`,
+    /// returning the index of the first bit in the run.
+    ///
+    /// Limitation: a run is only ever searched for within a single leaf
+    /// (see [`Bitmap32::alloc_contiguous`]), so `size` can never exceed 32,
+    /// and a run that would straddle two leaves is never found even when
+    /// both halves are individually free.
+    fn alloc_contiguous(&mut self, size: usize, align_log2: usize) -> Option<usize>;
+
+    /// Free the bit at `index`.
+    fn dealloc(&mut self, index: usize);
+
+    /// Mark `index` allocated directly, without searching for it.
+    ///
+    /// For callers that are handing out a specific, previously-unclaimed
+    /// index (e.g. extending a bump cursor) rather than asking "give me any
+    /// free bit". `index` must currently be clear.
+    fn mark_allocated(&mut self, index: usize);
+
+    /// Whether every bit is allocated.
+    fn is_full(&self) -> bool;
+
+    /// Number of allocated bits.
+    fn count_allocated(&self) -> usize;
+}
+
+/// Leaf bitmap: 32 individually addressable bits packed into a `u32`.
+///
+/// A set bit means "allocated"; `bits == u32::MAX` means the leaf is full.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Bitmap32(u32);
+
+impl Bitmap32 {
+    const CAP: usize = 32;
+
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    fn test(&self, index: usize) -> bool {
+        self.0 & (1 << (Self::CAP - 1 - index)) != 0
+    }
+
+    fn set(&mut self, index: usize) {
+        self.0 |= 1 << (Self::CAP - 1 - index);
+    }
+
+    fn clear(&mut self, index: usize) {
+        self.0 &= !(1 << (Self::CAP - 1 - index));
+    }
+
+    /// Find the first clear bit. Fast path via `leading_zeros()`; falls
+    /// back to a linear scan if the fast path ever disagrees with the
+    /// bitmap contents (defends against the two ever drifting apart).
+    fn alloc_bits(&self) -> Option<usize> {
+        if self.0 == u32::MAX {
+            return None;
+        }
+        let fast = (!self.0).leading_zeros() as usize;
+        if !self.test(fast) {
+            return Some(fast);
+        }
+        (0..Self::CAP).find(|&i| !self.test(i))
+    }
+}
+
+impl BitAlloc for Bitmap32 {
+    const CAP: usize = Self::CAP;
+
+    fn alloc(&mut self) -> Option<usize> {
+        let index = self.alloc_bits()?;
+        self.set(index);
+        Some(index)
+    }
+
+    /// Scans this leaf's 32 bits for a clear, aligned run of `size` bits.
+    /// Always fails for `size > 32`: a leaf has nowhere else to look.
+    fn alloc_contiguous(&mut self, size: usize, align_log2: usize) -> Option<usize> {
+        if size == 0 || size > Self::CAP {
+            return None;
+        }
+        let align = 1usize << align_log2;
+        let mut start = 0;
+        while start + size <= Self::CAP {
+            if start % align != 0 {
+                start += align - start % align;
+                continue;
+            }
+            if (start..start + size).all(|i| !self.test(i)) {
+                for i in start..start + size {
+                    self.set(i);
+                }
+                return Some(start);
+            }
+            start += 1;
+        }
+        None
+    }
+
+    fn dealloc(&mut self, index: usize) {
+        self.clear(index);
+    }
+
+    fn mark_allocated(&mut self, index: usize) {
+        self.set(index);
+    }
+
+    fn is_full(&self) -> bool {
+        self.0 == u32::MAX
+    }
+
+    fn count_allocated(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+}
+
+/// Two-level cascade: a `bits` summary word where bit `i` is set only when
+/// `next[i]` is completely full, plus the 32 children themselves.
+#[derive(Debug, Clone, Copy)]
+pub struct Bitmap<B: BitAlloc> {
+    bits: u32,
+    next: [B; 32],
+}
+
+impl<B: BitAlloc> Default for Bitmap<B> {
+    fn default() -> Self {
+        Self {
+            bits: 0,
+            next: [B::default(); 32],
+        }
+    }
+}
+
+impl Bitmap<Bitmap32> {
+    /// Const constructor for the concrete two-level page bitmap, so it can
+    /// be used as the initial value of a `const fn new()`.
+    pub const fn new_const() -> Self {
+        Self {
+            bits: 0,
+            next: [Bitmap32::new(); 32],
+        }
+    }
+}
+
+impl<B: BitAlloc> Bitmap<B> {
+    /// Find the first child whose summary bit is clear, i.e. not full.
+    fn first_non_full_child(&self) -> Option<usize> {
+        if self.bits == u32::MAX {
+            return None;
+        }
+        let fast = (!self.bits).leading_zeros() as usize;
+        if self.bits & (1 << (32 - 1 - fast)) == 0 {
+            return Some(fast);
+        }
+        (0..32).find(|&i| self.bits & (1 << (32 - 1 - i)) == 0)
+    }
+}
+
+impl<B: BitAlloc> BitAlloc for Bitmap<B> {
+    const CAP: usize = 32 * B::CAP;
+
+    fn alloc(&mut self) -> Option<usize> {
+        let i = self.first_non_full_child()?;
+        let off = self.next[i].alloc()?;
+        if self.next[i].is_full() {
+            self.bits |= 1 << (32 - 1 - i);
+        }
+        Some(i * B::CAP + off)
+    }
+
+    /// Delegates to each non-full child in turn. Does not attempt to join
+    /// a run across two children, so a contiguous request can only ever be
+    /// satisfied within a single child's capacity (see the trait-level
+    /// doc on [`BitAlloc::alloc_contiguous`]).
+    fn alloc_contiguous(&mut self, size: usize, align_log2: usize) -> Option<usize> {
+        for i in 0..32 {
+            if self.bits & (1 << (32 - 1 - i)) != 0 {
+                continue;
+            }
+            if let Some(off) = self.next[i].alloc_contiguous(size, align_log2) {
+                if self.next[i].is_full() {
+                    self.bits |= 1 << (32 - 1 - i);
+                }
+                return Some(i * B::CAP + off);
+            }
+        }
+        None
+    }
+
+    fn dealloc(&mut self, index: usize) {
+        let i = index / B::CAP;
+        let off = index % B::CAP;
+        self.next[i].dealloc(off);
+        // The child can no longer be full once any of its bits is freed.
+        self.bits &= !(1 << (32 - 1 - i));
+    }
+
+    fn mark_allocated(&mut self, index: usize) {
+        let i = index / B::CAP;
+        let off = index % B::CAP;
+        self.next[i].mark_allocated(off);
+        if self.next[i].is_full() {
+            self.bits |= 1 << (32 - 1 - i);
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.bits == u32::MAX
+    }
+
+    fn count_allocated(&self) -> usize {
+        self.next.iter().map(|c| c.count_allocated()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bitmap32_alloc_fills_in_order_then_reports_full() {
+        let mut b = Bitmap32::new();
+        for i in 0..32 {
+            assert_eq!(b.alloc(), Some(i));
+        }
+        assert!(b.is_full());
+        assert_eq!(b.alloc(), None);
+        assert_eq!(b.count_allocated(), 32);
+    }
+
+    #[test]
+    fn bitmap32_dealloc_then_realloc_reuses_the_freed_bit() {
+        let mut b = Bitmap32::new();
+        let a = b.alloc().unwrap();
+        let _ = b.alloc().unwrap();
+        b.dealloc(a);
+        assert_eq!(b.count_allocated(), 1);
+        assert_eq!(b.alloc(), Some(a));
+    }
+
+    #[test]
+    fn bitmap32_alloc_contiguous_respects_alignment_and_size() {
+        let mut b = Bitmap32::new();
+        // Force indices 0..4 to be occupied so the next run must skip them.
+        for _ in 0..4 {
+            b.alloc().unwrap();
+        }
+        // size larger than the leaf's capacity can never be satisfied.
+        assert_eq!(b.alloc_contiguous(33, 0), None);
+        // A 4-aligned run of 4 must land at index 4, not 1..5.
+        let start = b.alloc_contiguous(4, 2).unwrap();
+        assert_eq!(start, 4);
+        assert!((4..8).all(|i| b.test(i)));
+    }
+
+    #[test]
+    fn bitmap32_alloc_contiguous_does_not_reuse_a_freed_hole_incorrectly() {
+        let mut b = Bitmap32::new();
+        for _ in 0..10 {
+            b.alloc().unwrap();
+        }
+        b.dealloc(3);
+        // The hole at index 3 is only 1 bit wide, too small for a run of 2.
+        let start = b.alloc_contiguous(2, 0).unwrap();
+        assert_eq!(start, 10);
+    }
+
+    #[test]
+    fn cascade_bitmap_allocates_into_first_child_then_moves_on() {
+        let mut b: Bitmap<Bitmap32> = Bitmap::default();
+        assert_eq!(Bitmap::<Bitmap32>::CAP, 1024);
+        for i in 0..32 {
+            assert_eq!(b.alloc(), Some(i));
+        }
+        // First child should now be marked full in the summary, so the
+        // next allocation must come from the second child (index 32).
+        assert_eq!(b.alloc(), Some(32));
+        assert_eq!(b.count_allocated(), 33);
+    }
+
+    #[test]
+    fn cascade_bitmap_dealloc_clears_summary_bit_for_reuse() {
+        let mut b: Bitmap<Bitmap32> = Bitmap::default();
+        for _ in 0..32 {
+            b.alloc().unwrap();
+        }
+        assert_eq!(b.alloc(), Some(32));
+        b.dealloc(0);
+        // Freeing one bit in the first (full) child must clear its summary
+        // bit so subsequent allocations can land there again.
+        assert_eq!(b.alloc(), Some(0));
+    }
+
+    #[test]
+    fn cascade_bitmap_exhausts_at_full_capacity() {
+        let mut b: Bitmap<Bitmap32> = Bitmap::default();
+        for i in 0..Bitmap::<Bitmap32>::CAP {
+            assert_eq!(b.alloc(), Some(i));
+        }
+        assert!(b.is_full());
+        assert_eq!(b.alloc(), None);
+    }
+
+    #[test]
+    fn mark_allocated_sets_the_bit_and_summary_without_searching() {
+        let mut b: Bitmap<Bitmap32> = Bitmap::default();
+        b.mark_allocated(40);
+        assert!(b.next[1].test(8));
+        assert_eq!(b.count_allocated(), 1);
+        // Marking the rest of that child full must also set its summary bit.
+        for i in 32..72 {
+            if i != 40 {
+                b.mark_allocated(i);
+            }
+        }
+        assert_ne!(b.bits & (1 << (32 - 1 - 1)), 0);
+        assert_eq!(b.alloc(), Some(0));
+    }
+
+    #[test]
+    fn cascade_bitmap_alloc_contiguous_never_straddles_children() {
+        let mut b: Bitmap<Bitmap32> = Bitmap::default();
+        // Leave only the last 2 bits of the first child and the first 2
+        // bits of the second child free (every later child is marked
+        // full): a run of 4 spanning the boundary
+        // exists logically, but must not be found by this implementation.
+        for i in 0..30 {
+            assert_eq!(b.alloc(), Some(i));
+        }
+        for i in 34..Bitmap::<Bitmap32>::CAP {
+            b.mark_allocated(i);
+        }
+        assert_eq!(b.count_allocated(), Bitmap::<Bitmap32>::CAP - 4);
+        assert_eq!(b.alloc_contiguous(4, 0), None);
+        // A run of 2 fits entirely within the first child's remaining bits.
+        assert_eq!(b.alloc_contiguous(2, 0), Some(30));
+    }
+}
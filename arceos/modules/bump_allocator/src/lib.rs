@@ -1,9 +1,23 @@
 #![no_std]
 
-use core::num;
-
 use allocator::{BaseAllocator, ByteAllocator, PageAllocator};
 
+mod alloc_api2;
+mod bitmap;
+
+pub use alloc_api2::AllocApi2Adapter;
+use bitmap::{BitAlloc, Bitmap, Bitmap32};
+
+/// Two-level bitmap tracking up to `32 * 32 = 1024` pages individually, so
+/// pages can be freed and reused out of order instead of only growing the
+/// `pages-used` area monotonically.
+///
+/// `PageBitmap::CAP` (1024) is a hard ceiling on how many pages `init` will
+/// ever claim for `page_bitmap` (see `page_capacity`), so `total_pages`/
+/// `available_pages` never claim more free space than the bitmap can
+/// actually hand out.
+type PageBitmap = Bitmap<Bitmap32>;
+
 /// Early memory allocator
 /// Use it before formal bytes-allocator and pages-allocator can work!
 /// This is a double-end memory range:
@@ -16,14 +30,33 @@ use allocator::{BaseAllocator, ByteAllocator, PageAllocator};
 ///
 /// For bytes area, 'count' records number of allocations.
 /// When it goes down to ZERO, free bytes-used area.
-/// For pages area, it will never be freed!
-///
+/// Bytes freed out of order (i.e. not the most recent allocation) are kept
+/// on `free_head`, an intrusive singly-linked free-list, and reused by
+/// `alloc`'s first-fit search before `b_pos` is bumped any further.
+/// For pages area, `p_pos` still bumps backward exactly as before to claim
+/// fresh territory from the shared middle region, but each claimed page is
+/// also given an index into `page_bitmap` (counting down from the
+/// page-aligned `end`), so it can be freed and reused out of order instead
+/// of only being reclaimable by shrinking `p_pos` again. `page_capacity`
+/// (at most `PageBitmap::CAP`) bounds how far `p_pos` is allowed to bump.
 pub struct EarlyAllocator<const SIZE: usize> {
     start: usize,
     end: usize,
     b_pos: usize,
     p_pos: usize,
     count: usize,
+    /// Head of the intrusive free-list of reclaimed byte blocks, or `None`.
+    /// Each free block stores a [`FreeBlockHeader`] at its own address.
+    free_head: Option<usize>,
+    /// Page-aligned upper bound of the page area, i.e. `end` rounded down
+    /// to a multiple of `SIZE`. Fixed at `init` time; page index `i` always
+    /// covers `[page_region_top - (i + 1) * SIZE, page_region_top - i * SIZE)`.
+    page_region_top: usize,
+    /// Maximum number of pages `p_pos` is ever allowed to claim: as many as
+    /// fit between `start` and `page_region_top`, capped at
+    /// `PageBitmap::CAP` since indices beyond that have no bit to occupy.
+    page_capacity: usize,
+    page_bitmap: PageBitmap,
 }
 
 impl<const SIZE: usize> EarlyAllocator<SIZE> {
@@ -34,8 +67,26 @@ impl<const SIZE: usize> EarlyAllocator<SIZE> {
             b_pos: 0,
             p_pos: 0,
             count: 0,
+            free_head: None,
+            page_region_top: 0,
+            page_capacity: 0,
+            page_bitmap: PageBitmap::new_const(),
         }
     }
+
+    /// Number of pages claimed so far out of `page_capacity`, i.e. how many
+    /// low bitmap indices currently correspond to real, claimed memory.
+    fn claimed_pages(&self) -> usize {
+        (self.page_region_top - self.p_pos) / SIZE
+    }
+
+    /// Start address of the `num_pages`-page run beginning at bitmap index
+    /// `index`. Indices count down from `page_region_top`, so the run's
+    /// lowest (returned) address is `num_pages` pages further from the top
+    /// than `index` alone.
+    fn page_index_to_addr(&self, index: usize, num_pages: usize) -> usize {
+        self.page_region_top - (index + num_pages) * SIZE
+    }
 }
 
 impl<const SIZE: usize> BaseAllocator for EarlyAllocator<SIZE> {
@@ -43,8 +94,14 @@ impl<const SIZE: usize> BaseAllocator for EarlyAllocator<SIZE> {
         self.start = start;
         self.end = start + size;
         self.b_pos = self.start;
-        self.p_pos = (self.end / SIZE) * SIZE; // align downward
         self.count = 0;
+        self.free_head = None;
+
+        self.page_region_top = (self.end / SIZE) * SIZE; // align downward
+        let max_region_pages = (self.page_region_top - self.start) / SIZE;
+        self.page_capacity = max_region_pages.min(PageBitmap::CAP);
+        self.p_pos = self.page_region_top;
+        self.page_bitmap = PageBitmap::default();
     }
 
     fn add_memory(&mut self, start: usize, size: usize) -> allocator::AllocResult {
@@ -52,11 +109,86 @@ impl<const SIZE: usize> BaseAllocator for EarlyAllocator<SIZE> {
     }
 }
 
+/// Header written into a freed byte block once it is pushed onto
+/// `EarlyAllocator::free_head`. `next` is `usize::MAX` as the `None`
+/// sentinel, since the block's own memory is all we have to store it in.
+///
+/// Invariant: a block must be at least `size_of::<FreeBlockHeader>()`
+/// (`size_of::<usize>() * 2`) bytes for this header to fit; smaller frees
+/// are simply leaked rather than corrupting neighboring memory.
+#[derive(Clone, Copy)]
+struct FreeBlockHeader {
+    next: usize,
+    size: usize,
+}
+
+const FREE_BLOCK_HEADER_SIZE: usize = core::mem::size_of::<FreeBlockHeader>();
+
+impl FreeBlockHeader {
+    /// SAFETY: `addr` must be within the bytes region and not currently in use.
+    unsafe fn read_at(addr: usize) -> Self {
+        (addr as *const FreeBlockHeader).read_unaligned()
+    }
+
+    /// SAFETY: `addr` must be within the bytes region and not currently in use.
+    unsafe fn write_at(addr: usize, header: FreeBlockHeader) {
+        (addr as *mut FreeBlockHeader).write_unaligned(header)
+    }
+}
+
+impl<const SIZE: usize> EarlyAllocator<SIZE> {
+    /// First-fit search of the free-list for a block that can satisfy
+    /// `layout`, splitting off and re-listing any leftover tail that is
+    /// itself big enough to hold a [`FreeBlockHeader`].
+    fn alloc_from_free_list(&mut self, layout: core::alloc::Layout) -> Option<usize> {
+        let align = layout.align();
+        let size = layout.size();
+
+        let mut prev = None;
+        let mut cur = self.free_head;
+        while let Some(addr) = cur {
+            // SAFETY: every address on the free-list points at a live FreeBlockHeader.
+            let header = unsafe { FreeBlockHeader::read_at(addr) };
+            if addr % align == 0 && header.size >= size {
+                let next = (header.next != usize::MAX).then_some(header.next);
+                match prev {
+                    Some(prev_addr) => {
+                        let mut prev_header = unsafe { FreeBlockHeader::read_at(prev_addr) };
+                        prev_header.next = header.next;
+                        unsafe { FreeBlockHeader::write_at(prev_addr, prev_header) };
+                    }
+                    None => self.free_head = next,
+                }
+
+                let remainder = header.size - size;
+                if remainder >= FREE_BLOCK_HEADER_SIZE {
+                    let tail_addr = addr + size;
+                    let tail = FreeBlockHeader {
+                        next: self.free_head.unwrap_or(usize::MAX),
+                        size: remainder,
+                    };
+                    unsafe { FreeBlockHeader::write_at(tail_addr, tail) };
+                    self.free_head = Some(tail_addr);
+                }
+                return Some(addr);
+            }
+            prev = Some(addr);
+            cur = (header.next != usize::MAX).then_some(header.next);
+        }
+        None
+    }
+}
+
 impl<const SIZE: usize> ByteAllocator for EarlyAllocator<SIZE> {
     fn alloc(
         &mut self,
         layout: core::alloc::Layout,
     ) -> allocator::AllocResult<core::ptr::NonNull<u8>> {
+        if let Some(addr) = self.alloc_from_free_list(layout) {
+            self.count += 1;
+            return Ok(core::ptr::NonNull::new(addr as *mut u8).unwrap());
+        }
+
         // Align b_pos upward, align has been guaranteed to be power of 2 by Layout
         let align = layout.align();
         let original_b_pos = self.b_pos;
@@ -87,12 +219,22 @@ impl<const SIZE: usize> ByteAllocator for EarlyAllocator<SIZE> {
         if self.count == 0 {
             // Free all byte allocations
             self.b_pos = self.start;
-        } else {
+            self.free_head = None;
+        } else if addr + layout.size() == self.b_pos {
             // Only free the last allocation, increase the limited utilization slightly
-            if addr + layout.size() == self.b_pos {
-                self.b_pos -= layout.size();
-            }
+            self.b_pos -= layout.size();
+        } else if layout.size() >= FREE_BLOCK_HEADER_SIZE {
+            // Interior block: push it onto the free-list for reuse instead of leaking it.
+            let header = FreeBlockHeader {
+                next: self.free_head.unwrap_or(usize::MAX),
+                size: layout.size(),
+            };
+            // SAFETY: `addr` was just validated above and is no longer in use.
+            unsafe { FreeBlockHeader::write_at(addr, header) };
+            self.free_head = Some(addr);
         }
+        // Blocks smaller than a FreeBlockHeader can't carry the free-list
+        // linkage and are simply leaked until the next full reset.
     }
 
     fn total_bytes(&self) -> usize {
@@ -111,39 +253,229 @@ impl<const SIZE: usize> ByteAllocator for EarlyAllocator<SIZE> {
 impl<const SIZE: usize> PageAllocator for EarlyAllocator<SIZE> {
     const PAGE_SIZE: usize = SIZE;
 
+    /// Limitation: freed pages are only reused for contiguous requests
+    /// (`num_pages > 1`, or any page-granularity alignment) out of a single
+    /// 32-page bitmap leaf (see [`Bitmap32::alloc_contiguous`]), so a
+    /// request for more than 32 pages, or a run that would straddle two
+    /// leaves, is never served from freed pages even when they are free.
+    /// Such a request is served only by claiming fresh pages with `p_pos`,
+    /// and fails with `NoMemory` when not enough fresh pages remain.
     fn alloc_pages(
         &mut self,
         num_pages: usize,
         align_pow2: usize,
     ) -> allocator::AllocResult<usize> {
-        let original_p_pos = self.p_pos;
-        // Align p_pos downward
-        self.p_pos -= num_pages * Self::PAGE_SIZE;
-        self.p_pos &= !(align_pow2 - 1);
-
-        // Check available pages
-        if self.available_pages() < num_pages {
-            self.b_pos = original_p_pos;
-            return Err(allocator::AllocError::NoMemory);
+        // align_pow2 is a byte alignment; page allocations are always at
+        // least page-aligned, so only alignments coarser than a page need
+        // to be translated into a page-granularity alignment for the
+        // bitmap search.
+        let align_pages = (align_pow2 / Self::PAGE_SIZE).max(1);
+        let align_log2 = align_pages.trailing_zeros() as usize;
+        let claimed = self.claimed_pages();
+
+        let candidate = if num_pages == 1 && align_log2 == 0 {
+            self.page_bitmap.alloc()
+        } else {
+            self.page_bitmap.alloc_contiguous(num_pages, align_log2)
+        };
+
+        // A hit only counts if it lands inside territory `p_pos` has
+        // actually claimed; anything beyond `claimed` is unclaimed memory
+        // that still belongs to the shared `avail-area` and must be
+        // claimed via the bump path below instead of handed out here.
+        if let Some(index) = candidate {
+            let addr = self.page_index_to_addr(index, num_pages);
+            if index + num_pages <= claimed && addr % align_pow2.max(SIZE) == 0 {
+                return Ok(addr);
+            }
+            for i in index..index + num_pages {
+                self.page_bitmap.dealloc(i);
+            }
         }
 
-        // Alloc pages
-        Ok(self.p_pos)
+        // Claim fresh territory from the shared middle region by bumping
+        // `p_pos` backward, exactly like a plain bump allocator; any
+        // alignment slack this introduces is marked allocated too (wasted,
+        // same as a bump allocator would waste it), and the pages actually
+        // being returned are marked allocated individually so they can
+        // later be freed and reused through the bitmap.
+        let page_region_floor = self.page_region_top - self.page_capacity * SIZE;
+        let new_p_pos = self
+            .p_pos
+            .checked_sub(num_pages * SIZE)
+            .map(|p| p & !(align_pow2.max(SIZE) - 1))
+            .filter(|&p| p >= page_region_floor && p >= self.b_pos)
+            .ok_or(allocator::AllocError::NoMemory)?;
+        self.p_pos = new_p_pos;
+
+        let new_claimed = self.claimed_pages();
+        for i in claimed..new_claimed {
+            self.page_bitmap.mark_allocated(i);
+        }
+        Ok(self.page_index_to_addr(new_claimed - num_pages, num_pages))
     }
 
     fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
-        unimplemented!("EarlyAllocator does not support dealloc_pages")
+        let index = (self.page_region_top - pos) / Self::PAGE_SIZE - num_pages;
+        for i in index..index + num_pages {
+            self.page_bitmap.dealloc(i);
+        }
+        // The bump cursor itself is never restored: pages are reused
+        // through the bitmap instead of by growing `p_pos` back.
     }
 
     fn total_pages(&self) -> usize {
-        (self.end - self.b_pos) / Self::PAGE_SIZE
+        self.page_capacity
     }
 
     fn used_pages(&self) -> usize {
-        (self.end - self.p_pos) / Self::PAGE_SIZE
+        self.page_bitmap.count_allocated()
     }
 
     fn available_pages(&self) -> usize {
-        self.available_bytes() / Self::PAGE_SIZE
+        self.total_pages() - self.used_pages()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::alloc::Layout;
+
+    #[repr(align(64))]
+    struct AlignedBuf([u8; 256]);
+
+    #[repr(align(4096))]
+    struct AlignedPages([u8; 64 * 4096]);
+
+    fn new_allocator(buf: &mut [u8]) -> EarlyAllocator<16> {
+        let mut a = EarlyAllocator::<16>::new();
+        a.init(buf.as_mut_ptr() as usize, buf.len());
+        a
+    }
+
+    #[test]
+    fn dealloc_of_tail_allocation_shrinks_b_pos() {
+        let mut buf = AlignedBuf([0u8; 256]);
+        let mut a = new_allocator(&mut buf.0);
+        let layout = Layout::from_size_align(16, 8).unwrap();
+        let _keep_alive = a.alloc(layout).unwrap();
+        let tail = a.alloc(layout).unwrap();
+        let used_before = a.used_bytes();
+        a.dealloc(tail, layout);
+        assert_eq!(a.used_bytes(), used_before - 16);
+        // The tail shrink path must not also touch the free-list.
+        assert!(a.free_head.is_none());
+    }
+
+    #[test]
+    fn interior_dealloc_is_reused_by_a_same_size_alloc() {
+        let mut buf = AlignedBuf([0u8; 256]);
+        let mut a = new_allocator(&mut buf.0);
+        let layout = Layout::from_size_align(32, 8).unwrap();
+        let p1 = a.alloc(layout).unwrap();
+        let p2 = a.alloc(layout).unwrap();
+        let _p3 = a.alloc(layout).unwrap(); // keep p2 from being the tail
+        a.dealloc(p2, layout);
+        // First-fit reuses the freed interior block instead of bumping b_pos.
+        let p4 = a.alloc(layout).unwrap();
+        assert_eq!(p4, p2);
+        let _ = p1;
+    }
+
+    #[test]
+    fn interior_dealloc_splits_remainder_back_onto_the_free_list() {
+        let mut buf = AlignedBuf([0u8; 256]);
+        let mut a = new_allocator(&mut buf.0);
+        let big = Layout::from_size_align(64, 8).unwrap();
+        let small = Layout::from_size_align(16, 8).unwrap();
+        let p1 = a.alloc(big).unwrap();
+        let _keep_tail = a.alloc(small).unwrap();
+        a.dealloc(p1, big);
+
+        // A smaller allocation carves out of the front of the freed block...
+        let p2 = a.alloc(small).unwrap();
+        assert_eq!(p2, p1);
+        // ...leaving a 48-byte remainder back on the free-list for reuse.
+        let p3 = a.alloc(small).unwrap();
+        assert_eq!(p3.as_ptr() as usize, p1.as_ptr() as usize + 16);
+    }
+
+    #[test]
+    fn too_small_interior_block_is_leaked_not_corrupted() {
+        let mut buf = AlignedBuf([0u8; 256]);
+        let mut a = new_allocator(&mut buf.0);
+        let tiny = Layout::from_size_align(1, 1).unwrap();
+        let big = Layout::from_size_align(64, 8).unwrap();
+        let p1 = a.alloc(tiny).unwrap();
+        let _keep_tail = a.alloc(big).unwrap();
+        let used_before = a.used_bytes();
+
+        a.dealloc(p1, tiny);
+
+        // Too small to hold a FreeBlockHeader: leaked, not linked into free_head.
+        assert!(a.free_head.is_none());
+        assert_eq!(a.used_bytes(), used_before);
+    }
+
+    #[test]
+    fn full_reset_clears_the_free_list() {
+        let mut buf = AlignedBuf([0u8; 256]);
+        let mut a = new_allocator(&mut buf.0);
+        let layout = Layout::from_size_align(32, 8).unwrap();
+        let p1 = a.alloc(layout).unwrap();
+        let p2 = a.alloc(layout).unwrap();
+
+        a.dealloc(p1, layout);
+        assert!(a.free_head.is_some());
+
+        a.dealloc(p2, layout);
+        assert!(a.free_head.is_none());
+        assert_eq!(a.used_bytes(), 0);
+    }
+
+    #[test]
+    fn pages_are_reused_after_dealloc_pages() {
+        let mut buf = AlignedPages([0u8; 64 * 4096]);
+        let mut a = EarlyAllocator::<4096>::new();
+        a.init(buf.0.as_mut_ptr() as usize, buf.0.len());
+
+        let p1 = a.alloc_pages(1, 4096).unwrap();
+        assert_eq!(a.used_pages(), 1);
+
+        a.dealloc_pages(p1, 1);
+        assert_eq!(a.used_pages(), 0);
+
+        let p2 = a.alloc_pages(1, 4096).unwrap();
+        assert_eq!(p2, p1);
+    }
+
+    #[test]
+    fn successive_page_allocations_claim_descending_addresses() {
+        let mut buf = AlignedPages([0u8; 64 * 4096]);
+        let mut a = EarlyAllocator::<4096>::new();
+        a.init(buf.0.as_mut_ptr() as usize, buf.0.len());
+
+        let p1 = a.alloc_pages(1, 4096).unwrap();
+        let p2 = a.alloc_pages(1, 4096).unwrap();
+        // Fresh claims still bump backward from the top, like a plain
+        // bump allocator, even though each page is now individually tracked.
+        assert_eq!(p2, p1 - 4096);
+        assert_eq!(a.used_pages(), 2);
+    }
+
+    #[test]
+    fn page_allocations_do_not_starve_the_byte_area_when_pages_are_unused() {
+        // A page-aligned buffer where `end` is already page-aligned used to
+        // make `total_bytes()` report zero, because the whole arena was
+        // reserved for pages up front regardless of whether any page was
+        // ever claimed.
+        let mut buf = AlignedPages([0u8; 64 * 4096]);
+        let mut a = EarlyAllocator::<4096>::new();
+        a.init(buf.0.as_mut_ptr() as usize, buf.0.len());
+
+        assert_eq!(a.total_bytes(), 64 * 4096);
+        let layout = Layout::from_size_align(16, 8).unwrap();
+        assert!(a.alloc(layout).is_ok());
     }
 }
@@ -4,10 +4,14 @@
 //      https://tinyzzh.github.io/rust/2023/04/10/rust_lang_tutorial_132_Trait_Hash.html
 
 use axhal::misc::random;
-use core::hash::Hash;
+use allocator_api2::alloc::{Allocator, Global};
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
 use hashbrown::HashMap;
 use foldhash::fast::FixedState;
 
+pub use hashbrown::TryReserveError;
+
 // Hasher: calculate hash value from input bytes
 // pub struct MyHasher {
 //     seed: u64,  // record the seed value, don't change after initialization
@@ -45,12 +49,13 @@ use foldhash::fast::FixedState;
 //     }
 // }
 
-// MyHashMap: hashmap using custom hasher
-pub struct MyHashMap<K, V> {
-    map: HashMap<K, V, FixedState>,
+// MyHashMap: hashmap using custom hasher, optionally backed by a
+// caller-supplied `allocator-api2` allocator instead of the global one.
+pub struct MyHashMap<K, V, S = FixedState, A: Allocator + Clone = Global> {
+    map: HashMap<K, V, S, A>,
 }
 
-impl<K, V> MyHashMap<K, V> {
+impl<K, V> MyHashMap<K, V, FixedState, Global> {
     pub fn new() -> Self
     where
         K: Hash + Eq,
@@ -60,32 +65,239 @@ impl<K, V> MyHashMap<K, V> {
             // map: HashMap::with_hasher(MyHasherBuilder),
         }
     }
+}
+
+impl<K, V, S> MyHashMap<K, V, S, Global> {
+    /// Build a map seeded with a caller-supplied `BuildHasher` instead of
+    /// `axhal::misc::random`, so e.g. tests can pin a deterministic seed.
+    pub fn with_hasher(state: S) -> Self
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        Self {
+            map: HashMap::with_hasher(state),
+        }
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> MyHashMap<K, V, S, A> {
+    /// Build a map backed by `alloc` instead of the global allocator, e.g.
+    /// an [`AllocApi2Adapter`](bump_allocator::AllocApi2Adapter) wrapping an
+    /// `EarlyAllocator`-owned region.
+    pub fn new_in(state: S, alloc: A) -> Self
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        Self {
+            map: HashMap::with_hasher_in(state, alloc),
+        }
+    }
 
     pub fn insert(&mut self, key: K, value: V)
-    where 
+    where
         K: Hash + Eq,
+        S: BuildHasher,
     {
         self.map.insert(key, value);
     }
 
-    pub fn get(&self, key: &K) -> Option<&V> 
-    where 
-        K: Hash + Eq,
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q> + Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+        S: BuildHasher,
     {
         self.map.get(key)
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<V> 
-    where 
-        K: Hash + Eq,
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q> + Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+        S: BuildHasher,
     {
         self.map.remove(key)
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> 
-    where 
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)>
+    where
         K: Hash + Eq,
     {
         self.map.iter()
     }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q> + Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+        S: BuildHasher,
+    {
+        self.map.contains_key(key)
+    }
+
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.map.retain(f)
+    }
+
+    /// Like [`insert`](Self::insert), but reports allocation failure instead
+    /// of aborting, by reserving capacity for the new entry up front via
+    /// hashbrown's `try_reserve`.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<Option<V>, TryReserveError>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        self.map.try_reserve(1)?;
+        Ok(self.map.insert(key, value))
+    }
+
+    /// Reserve capacity for at least `additional` more elements without
+    /// panicking on OOM.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        self.map.try_reserve(additional)
+    }
+
+    /// Get-or-insert without double hashing: the returned [`Entry`] already
+    /// holds the bucket found for `key`, mirroring
+    /// `hashbrown::HashMap::entry`.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S, A>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        match self.map.entry(key) {
+            hashbrown::hash_map::Entry::Occupied(e) => Entry::Occupied(e),
+            hashbrown::hash_map::Entry::Vacant(e) => Entry::Vacant(e),
+        }
+    }
+}
+
+/// A view into a single entry of a [`MyHashMap`], obtained via
+/// [`MyHashMap::entry`]. Mirrors `std::collections::hash_map::Entry`.
+pub enum Entry<'a, K, V, S = FixedState, A: Allocator + Clone = Global> {
+    Occupied(hashbrown::hash_map::OccupiedEntry<'a, K, V, S, A>),
+    Vacant(hashbrown::hash_map::VacantEntry<'a, K, V, S, A>),
+}
+
+impl<'a, K, V, S, A: Allocator + Clone> Entry<'a, K, V, S, A>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn and_modify<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::{String, ToString};
+    use core::cell::Cell;
+
+    /// Deterministic in place of `MyHashMap::new`'s `axhal::misc::random`
+    /// seed, exactly the use case `with_hasher` was added for.
+    fn map_with_hasher<K: Hash + Eq, V>() -> MyHashMap<K, V> {
+        MyHashMap::with_hasher(FixedState::with_seed(0))
+    }
+
+    #[test]
+    fn borrow_based_lookup_finds_a_string_key_by_str() {
+        let mut m = map_with_hasher::<String, i32>();
+        m.insert("hello".to_string(), 1);
+
+        assert_eq!(m.get("hello"), Some(&1));
+        assert!(m.contains_key("hello"));
+        assert_eq!(m.remove("hello"), Some(1));
+        assert!(!m.contains_key("hello"));
+    }
+
+    #[test]
+    fn try_insert_and_try_reserve_succeed_on_the_happy_path() {
+        let mut m = map_with_hasher::<i32, i32>();
+        assert!(m.try_reserve(4).is_ok());
+        assert_eq!(m.try_insert(1, 10).unwrap(), None);
+        // try_insert on an existing key behaves like insert: overwrites and
+        // returns the old value.
+        assert_eq!(m.try_insert(1, 20).unwrap(), Some(10));
+        assert_eq!(m.get(&1), Some(&20));
+    }
+
+    #[test]
+    fn or_insert_with_calls_the_closure_only_for_a_vacant_entry() {
+        let mut m = map_with_hasher::<i32, i32>();
+        let calls = Cell::new(0);
+
+        let v = m.entry(1).or_insert_with(|| {
+            calls.set(calls.get() + 1);
+            42
+        });
+        assert_eq!(*v, 42);
+        assert_eq!(calls.get(), 1);
+
+        // The entry is now occupied, so a second or_insert_with must not
+        // call the closure (or overwrite the existing value) at all.
+        let v2 = m.entry(1).or_insert_with(|| {
+            calls.set(calls.get() + 1);
+            99
+        });
+        assert_eq!(*v2, 42);
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn and_modify_on_a_vacant_entry_is_a_no_op() {
+        let mut m = map_with_hasher::<i32, i32>();
+        let v = m.entry(1).and_modify(|v| *v += 100).or_insert(5);
+        // The key didn't exist yet, so and_modify's closure never ran: the
+        // value comes entirely from or_insert, not 5 + 100.
+        assert_eq!(*v, 5);
+    }
 }